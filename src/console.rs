@@ -11,41 +11,57 @@ use std::time::{Duration, Instant};
 use ::console::{style, StyledObject};
 use camino::Utf8Path;
 
-use tracing::Level;
+use tracing::{warn, Level};
 use tracing_subscriber::fmt::MakeWriter;
 use tracing_subscriber::prelude::*;
 
 use crate::outcome::{LabOutcome, SummaryOutcome};
+use crate::reporter::Reporter;
 use crate::*;
 
 static COPY_MESSAGE: &str = "Copy source to scratch directory";
 
 /// An interface to the console for the rest of cargo-mutants.
 ///
-/// This wraps the Nutmeg view and model.
+/// This wraps the Nutmeg view and model, and also fans each event out to
+/// any other configured [Reporter]s (for example a [crate::reporter::JsonlReporter]
+/// writing a machine-readable event stream), so that a human watching the
+/// progress bar and a CI system parsing NDJSON can observe the same run at
+/// the same time.
 pub struct Console {
-    /// The inner view through which progress bars and messages are drawn.
-    view: Arc<nutmeg::View<LabModel>>,
+    /// The terminal progress bar and message log, itself a `Reporter`.
+    terminal: Arc<TerminalReporter>,
 
-    /// The `mutants.out/debug.log` file, if it's open yet.
-    debug_log: Arc<Mutex<Option<File>>>,
+    /// Other reporters (e.g. NDJSON) that should see every event alongside
+    /// the terminal.
+    extra_reporters: Vec<Box<dyn Reporter>>,
 }
 
 impl Console {
     pub fn new() -> Console {
         Console {
-            view: Arc::new(nutmeg::View::new(LabModel::default(), nutmeg_options())),
-            debug_log: Arc::new(Mutex::new(None)),
+            terminal: Arc::new(TerminalReporter::new()),
+            extra_reporters: Vec::new(),
+        }
+    }
+
+    /// Add another reporter (such as a [crate::reporter::JsonlReporter]) that should
+    /// receive every event alongside the terminal view.
+    pub fn add_reporter(&mut self, reporter: Box<dyn Reporter>) {
+        self.extra_reporters.push(reporter);
+    }
+
+    /// Run `f` against the terminal reporter and every extra reporter.
+    fn dispatch(&self, f: impl Fn(&dyn Reporter)) {
+        f(self.terminal.as_ref());
+        for reporter in &self.extra_reporters {
+            f(reporter.as_ref());
         }
     }
 
     /// Update that a cargo task is starting.
     pub fn scenario_started(&self, scenario: &Scenario, log_file: &Utf8Path) {
-        let start = Instant::now();
-        let scenario_model = ScenarioModel::new(scenario, start, log_file.to_owned());
-        self.view.update(|model| {
-            model.scenario_models.push(scenario_model);
-        });
+        self.dispatch(|r| r.scenario_started(scenario, log_file));
     }
 
     /// Update that cargo finished.
@@ -55,6 +71,239 @@ impl Console {
         outcome: &ScenarioOutcome,
         options: &Options,
     ) {
+        self.dispatch(|r| r.scenario_finished(scenario, outcome, options));
+    }
+
+    /// Update that a test timeout was auto-set.
+    pub fn autoset_timeout(&self, timeout: Duration) {
+        self.message(&format!(
+            "Auto-set test timeout to {}\n",
+            style_secs(timeout)
+        ));
+    }
+
+    pub fn build_dirs_start(&self, _n: usize) {
+        // self.message(&format!("Make {n} more build directories...\n"));
+    }
+
+    pub fn build_dirs_finished(&self) {}
+
+    /// Start the "copy source to scratch directory" phase.
+    ///
+    /// `total_bytes` is the size of the source tree, measured up front, if
+    /// known, so that an ETA can be shown alongside the copy rate.
+    pub fn start_copy(&self, total_bytes: Option<u64>) {
+        self.terminal.start_copy(total_bytes);
+    }
+
+    pub fn finish_copy(&self) {
+        self.terminal.finish_copy();
+    }
+
+    pub fn copy_progress(&self, total_bytes: u64) {
+        self.terminal.copy_progress(total_bytes);
+    }
+
+    /// In `--watch` mode, show that we're idle and waiting for the source
+    /// tree to change.
+    pub fn watching(&self, n_files: usize, n_mutants: usize) {
+        self.terminal.watching(n_files, n_mutants);
+    }
+
+    /// In `--watch` mode, a batch of changed files produced a new set of
+    /// affected mutants to retest.
+    pub fn resume_watching_batch(&self, n_mutants: usize) {
+        self.terminal.resume_watching_batch(n_mutants);
+    }
+
+    /// Update that we discovered some mutants to test.
+    ///
+    /// `shuffle_seed` is set if `--shuffle` randomized the testing order, so
+    /// that it can be printed and the run reproduced later.
+    pub fn discovered_mutants(&self, mutants: &[Mutant], shuffle_seed: Option<u64>) {
+        self.dispatch(|r| r.discovered_mutants(mutants, shuffle_seed));
+    }
+
+    /// Update that work is starting on testing a given number of mutants.
+    pub fn start_testing_mutants(&self, _n_mutants: usize) {
+        self.terminal.start_testing_mutants();
+    }
+
+    /// A new phase of this scenario started.
+    pub fn scenario_phase_started(&self, scenario: &Scenario, phase: Phase) {
+        self.dispatch(|r| r.scenario_phase_started(scenario, phase));
+    }
+
+    pub fn scenario_phase_finished(&self, scenario: &Scenario, phase: Phase) {
+        self.dispatch(|r| r.scenario_phase_finished(scenario, phase));
+    }
+
+    pub fn lab_finished(
+        &self,
+        lab_outcome: &LabOutcome,
+        start_time: Instant,
+        output_dir: &Utf8Path,
+        options: &Options,
+    ) {
+        self.dispatch(|r| r.lab_finished(lab_outcome, start_time, options));
+        if let Err(err) = crate::junit::write_junit_report(lab_outcome, output_dir) {
+            warn!("failed to write JUnit report: {err}");
+        }
+    }
+
+    pub fn message(&self, message: &str) {
+        self.terminal.message(message)
+    }
+
+    pub fn tick(&self) {
+        self.terminal.tick()
+    }
+
+    /// Return a tracing `MakeWriter` that will send messages via nutmeg to the console.
+    pub fn make_terminal_writer(&self) -> TerminalWriter {
+        self.terminal.make_terminal_writer()
+    }
+
+    /// Return a tracing `MakeWriter` that will send messages to the debug log file if
+    /// it's open.
+    pub fn make_debug_log_writer(&self) -> DebugLogWriter {
+        self.terminal.make_debug_log_writer()
+    }
+
+    /// Set the debug log file.
+    pub fn set_debug_log(&self, file: File) {
+        self.terminal.set_debug_log(file);
+    }
+
+    /// Configure tracing to send messages to the console and debug log.
+    ///
+    /// The debug log is opened later and provided by [Console::set_debug_log].
+    pub fn setup_global_trace(&self, console_trace_level: Level) -> Result<()> {
+        self.terminal.setup_global_trace(console_trace_level)
+    }
+}
+
+/// The terminal (nutmeg-backed) implementation of [Reporter].
+///
+/// This is what used to be all of `Console`'s behavior; it's now one
+/// `Reporter` among potentially several, so that e.g. a NDJSON reporter can
+/// run at the same time without its output getting tangled up in the
+/// progress bar's control sequences.
+pub struct TerminalReporter {
+    /// The inner view through which progress bars and messages are drawn.
+    view: Arc<nutmeg::View<LabModel>>,
+
+    /// The `mutants.out/debug.log` file, if it's open yet.
+    debug_log: Arc<Mutex<Option<File>>>,
+}
+
+impl TerminalReporter {
+    fn new() -> TerminalReporter {
+        TerminalReporter {
+            view: Arc::new(nutmeg::View::new(LabModel::default(), nutmeg_options())),
+            debug_log: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    fn start_copy(&self, total_bytes: Option<u64>) {
+        self.view.update(|model| {
+            assert!(model.copy_model.is_none());
+            model.copy_model = Some(CopyModel::new(total_bytes));
+        });
+    }
+
+    fn finish_copy(&self) {
+        self.view.update(|model| {
+            model.copy_model = None;
+        });
+    }
+
+    fn copy_progress(&self, total_bytes: u64) {
+        self.view.update(|model| {
+            model
+                .copy_model
+                .as_mut()
+                .expect("copy in progress")
+                .bytes_copied(total_bytes)
+        });
+    }
+
+    fn start_testing_mutants(&self) {
+        self.view
+            .update(|model| model.mutants_start_time = Some(Instant::now()));
+    }
+
+    fn watching(&self, n_files: usize, n_mutants: usize) {
+        self.view
+            .update(|model| model.start_watching(n_files, n_mutants));
+    }
+
+    fn resume_watching_batch(&self, n_mutants: usize) {
+        self.view
+            .update(|model| model.start_watch_batch(n_mutants));
+    }
+
+    fn message(&self, message: &str) {
+        self.view.message(message)
+    }
+
+    fn tick(&self) {
+        self.view.update(|_| ())
+    }
+
+    /// Return a tracing `MakeWriter` that will send messages via nutmeg to the console.
+    fn make_terminal_writer(&self) -> TerminalWriter {
+        TerminalWriter {
+            view: Arc::clone(&self.view),
+        }
+    }
+
+    /// Return a tracing `MakeWriter` that will send messages to the debug log file if
+    /// it's open.
+    fn make_debug_log_writer(&self) -> DebugLogWriter {
+        DebugLogWriter(Arc::clone(&self.debug_log))
+    }
+
+    /// Set the debug log file.
+    fn set_debug_log(&self, file: File) {
+        *self.debug_log.lock().unwrap() = Some(file);
+    }
+
+    /// Configure tracing to send messages to the console and debug log.
+    fn setup_global_trace(&self, console_trace_level: Level) -> Result<()> {
+        // Show time relative to the start of the program.
+        let uptime = tracing_subscriber::fmt::time::uptime();
+        let debug_log_layer = tracing_subscriber::fmt::layer()
+            .with_ansi(false)
+            .with_file(true) // source file name
+            .with_line_number(true)
+            .with_timer(uptime)
+            .with_writer(self.make_debug_log_writer());
+        let level_filter = tracing_subscriber::filter::LevelFilter::from_level(console_trace_level);
+        let console_layer = tracing_subscriber::fmt::layer()
+            .with_ansi(true)
+            .with_writer(self.make_terminal_writer())
+            .with_target(false)
+            .with_timer(uptime)
+            .with_filter(level_filter);
+        tracing_subscriber::registry()
+            .with(debug_log_layer)
+            .with(console_layer)
+            .init();
+        Ok(())
+    }
+}
+
+impl Reporter for TerminalReporter {
+    fn scenario_started(&self, scenario: &Scenario, log_file: &Utf8Path) {
+        let start = Instant::now();
+        let scenario_model = ScenarioModel::new(scenario, start, log_file.to_owned());
+        self.view.update(|model| {
+            model.scenario_models.push(scenario_model);
+        });
+    }
+
+    fn scenario_finished(&self, scenario: &Scenario, outcome: &ScenarioOutcome, options: &Options) {
         self.view.update(|model| {
             model.mutants_done += scenario.is_mutant() as usize;
             match outcome.summary() {
@@ -111,49 +360,18 @@ impl Console {
         self.view.message(&s);
     }
 
-    /// Update that a test timeout was auto-set.
-    pub fn autoset_timeout(&self, timeout: Duration) {
-        self.message(&format!(
-            "Auto-set test timeout to {}\n",
-            style_secs(timeout)
-        ));
-    }
-
-    pub fn build_dirs_start(&self, _n: usize) {
-        // self.message(&format!("Make {n} more build directories...\n"));
-    }
-
-    pub fn build_dirs_finished(&self) {}
-
-    // pub fn start_copy(&self) {
-    //     self.view.update(|model| {
-    //         assert!(model.copy_model.is_none());
-    //         model.copy_model = Some(CopyModel::new());
-    //     });
-    // }
-
-    // pub fn finish_copy(&self) {
-    //     self.view.update(|model| {
-    //         model.copy_model = None;
-    //     });
-    // }
-
-    // pub fn copy_progress(&self, total_bytes: u64) {
-    //     self.view.update(|model| {
-    //         model
-    //             .copy_model
-    //             .as_mut()
-    //             .expect("copy in progress")
-    //             .bytes_copied(total_bytes)
-    //     });
-    // }
-
-    /// Update that we discovered some mutants to test.
-    pub fn discovered_mutants(&self, mutants: &[Mutant]) {
-        self.message(&format!(
-            "Found {} to test\n",
-            plural(mutants.len(), "mutant")
-        ));
+    fn discovered_mutants(&self, mutants: &[Mutant], shuffle_seed: Option<u64>) {
+        if let Some(seed) = shuffle_seed {
+            self.message(&format!(
+                "Testing {}, shuffle seed 0x{seed:x}\n",
+                plural(mutants.len(), "mutant")
+            ));
+        } else {
+            self.message(&format!(
+                "Found {} to test\n",
+                plural(mutants.len(), "mutant")
+            ));
+        }
         let n_mutants = mutants.len();
         self.view.update(|model| {
             model.n_mutants = n_mutants;
@@ -161,26 +379,19 @@ impl Console {
         })
     }
 
-    /// Update that work is starting on testing a given number of mutants.
-    pub fn start_testing_mutants(&self, _n_mutants: usize) {
-        self.view
-            .update(|model| model.mutants_start_time = Some(Instant::now()));
-    }
-
-    /// A new phase of this scenario started.
-    pub fn scenario_phase_started(&self, scenario: &Scenario, phase: Phase) {
+    fn scenario_phase_started(&self, scenario: &Scenario, phase: Phase) {
         self.view.update(|model| {
             model.find_scenario_mut(scenario).phase_started(phase);
         })
     }
 
-    pub fn scenario_phase_finished(&self, scenario: &Scenario, phase: Phase) {
+    fn scenario_phase_finished(&self, scenario: &Scenario, phase: Phase) {
         self.view.update(|model| {
             model.find_scenario_mut(scenario).phase_finished(phase);
         })
     }
 
-    pub fn lab_finished(&self, lab_outcome: &LabOutcome, start_time: Instant, options: &Options) {
+    fn lab_finished(&self, lab_outcome: &LabOutcome, start_time: Instant, options: &Options) {
         self.view.update(|model| {
             model.scenario_models.clear();
         });
@@ -189,58 +400,6 @@ impl Console {
             lab_outcome.summary_string(start_time, options)
         ));
     }
-
-    pub fn message(&self, message: &str) {
-        self.view.message(message)
-    }
-
-    pub fn tick(&self) {
-        self.view.update(|_| ())
-    }
-
-    /// Return a tracing `MakeWriter` that will send messages via nutmeg to the console.
-    pub fn make_terminal_writer(&self) -> TerminalWriter {
-        TerminalWriter {
-            view: Arc::clone(&self.view),
-        }
-    }
-
-    /// Return a tracing `MakeWriter` that will send messages to the debug log file if
-    /// it's open.
-    pub fn make_debug_log_writer(&self) -> DebugLogWriter {
-        DebugLogWriter(Arc::clone(&self.debug_log))
-    }
-
-    /// Set the debug log file.
-    pub fn set_debug_log(&self, file: File) {
-        *self.debug_log.lock().unwrap() = Some(file);
-    }
-
-    /// Configure tracing to send messages to the console and debug log.
-    ///
-    /// The debug log is opened later and provided by [Console::set_debug_log].
-    pub fn setup_global_trace(&self, console_trace_level: Level) -> Result<()> {
-        // Show time relative to the start of the program.
-        let uptime = tracing_subscriber::fmt::time::uptime();
-        let debug_log_layer = tracing_subscriber::fmt::layer()
-            .with_ansi(false)
-            .with_file(true) // source file name
-            .with_line_number(true)
-            .with_timer(uptime)
-            .with_writer(self.make_debug_log_writer());
-        let level_filter = tracing_subscriber::filter::LevelFilter::from_level(console_trace_level);
-        let console_layer = tracing_subscriber::fmt::layer()
-            .with_ansi(true)
-            .with_writer(self.make_terminal_writer())
-            .with_target(false)
-            .with_timer(uptime)
-            .with_filter(level_filter);
-        tracing_subscriber::registry()
-            .with(debug_log_layer)
-            .with(console_layer)
-            .init();
-        Ok(())
-    }
 }
 
 /// Write trace output to the terminal via the console.
@@ -301,12 +460,25 @@ impl io::Write for DebugLogWriter {
     }
 }
 
+/// Whether the lab is actively running scenarios, or idle between runs
+/// because `--watch` is waiting for the source tree to change.
+#[derive(Default)]
+enum LabState {
+    #[default]
+    Running,
+    Watching {
+        n_files: usize,
+        n_mutants: usize,
+    },
+}
+
 /// Description of all current activities in the lab.
 ///
 /// At the moment there is either a copy, cargo runs, or nothing.  Later, there
 /// might be concurrent activities.
 #[derive(Default)]
 struct LabModel {
+    state: LabState,
     copy_model: Option<CopyModel>,
     scenario_models: Vec<ScenarioModel>,
     lab_start_time: Option<Instant>,
@@ -325,6 +497,14 @@ struct LabModel {
 impl nutmeg::Model for LabModel {
     fn render(&mut self, width: usize) -> String {
         let mut s = String::with_capacity(100);
+        if let LabState::Watching { n_files, n_mutants } = &self.state {
+            return format!(
+                "{} \u{2014} watching for changes, {}, {}",
+                style("Idle").cyan(),
+                plural(*n_files, "file"),
+                plural(*n_mutants, "mutant"),
+            );
+        }
         if let Some(copy) = self.copy_model.as_mut() {
             s.push_str(&copy.render(width));
         }
@@ -415,6 +595,21 @@ impl LabModel {
     fn remove_scenario(&mut self, scenario: &Scenario) {
         self.scenario_models.retain(|sm| sm.scenario != *scenario);
     }
+
+    /// Switch to idle, waiting for the next batch of changes in `--watch` mode.
+    fn start_watching(&mut self, n_files: usize, n_mutants: usize) {
+        self.state = LabState::Watching { n_files, n_mutants };
+    }
+
+    /// Switch back to running a freshly recomputed batch of affected mutants.
+    fn start_watch_batch(&mut self, n_mutants: usize) {
+        self.state = LabState::Running;
+        self.scenario_models.clear();
+        self.lab_start_time = Some(Instant::now());
+        self.mutants_start_time = None;
+        self.mutants_done = 0;
+        self.n_mutants = n_mutants;
+    }
 }
 
 /// A Nutmeg progress model for running a single scenario.
@@ -482,35 +677,53 @@ impl nutmeg::Model for ScenarioModel {
 /// A Nutmeg model for progress in copying a tree.
 struct CopyModel {
     bytes_copied: u64,
+    /// The total size of the source tree, measured up front, if known.
+    total_bytes: Option<u64>,
     start: Instant,
 }
 
 impl CopyModel {
-    #[allow(dead_code)]
-    fn new() -> CopyModel {
+    fn new(total_bytes: Option<u64>) -> CopyModel {
         CopyModel {
             start: Instant::now(),
             bytes_copied: 0,
+            total_bytes,
         }
     }
 
     /// Update that some bytes have been copied.
     ///
     /// `bytes_copied` is the total bytes copied so far.
-    #[allow(dead_code)]
     fn bytes_copied(&mut self, bytes_copied: u64) {
         self.bytes_copied = bytes_copied
     }
+
+    /// Bytes per second copied so far, if any time has passed.
+    fn bytes_per_sec(&self) -> Option<f64> {
+        let elapsed = self.start.elapsed().as_secs_f64();
+        (elapsed > 0.0).then(|| self.bytes_copied as f64 / elapsed)
+    }
 }
 
 impl nutmeg::Model for CopyModel {
     fn render(&mut self, _width: usize) -> String {
-        format!(
+        let mut s = format!(
             "{} ... {} in {}",
             COPY_MESSAGE,
             style_mb(self.bytes_copied),
             style_elapsed_secs(self.start),
-        )
+        );
+        if let Some(rate) = self.bytes_per_sec() {
+            write!(s, ", {}", style_rate(rate)).unwrap();
+            if let Some(total_bytes) = self.total_bytes {
+                let remaining_bytes = total_bytes.saturating_sub(self.bytes_copied);
+                if remaining_bytes > 0 && rate > 0.0 {
+                    let eta = Duration::from_secs_f64(remaining_bytes as f64 / rate);
+                    write!(s, ", about {} remaining", style_minutes_seconds(eta)).unwrap();
+                }
+            }
+        }
+        s
     }
 }
 
@@ -583,6 +796,10 @@ fn style_mb(bytes: u64) -> StyledObject<String> {
     style(format_mb(bytes)).cyan()
 }
 
+fn style_rate(bytes_per_sec: f64) -> StyledObject<String> {
+    style(format!("{:.1} MB/s", bytes_per_sec / 1_000_000.0)).cyan()
+}
+
 pub fn style_scenario(scenario: &Scenario) -> Cow<'static, str> {
     match scenario {
         Scenario::Baseline => "Unmutated baseline".into(),
@@ -590,6 +807,43 @@ pub fn style_scenario(scenario: &Scenario) -> Cow<'static, str> {
     }
 }
 
+/// Describe a scenario with no ANSI styling, for consumers that need plain
+/// text rather than something meant for a terminal: the NDJSON reporter and
+/// the JUnit report.
+pub fn plain_scenario(scenario: &Scenario) -> String {
+    strip_ansi(&style_scenario(scenario))
+}
+
+/// Remove ANSI escape sequences (e.g. `\x1b[1m`, `\x1b[0m`) from a string,
+/// leaving only the plain text they were decorating.
+///
+/// This is more thorough than just filtering the ESC control byte: that
+/// alone leaves the rest of the escape sequence (`[1m`, `[0m`, ...) behind
+/// as literal garbage.
+pub fn strip_ansi(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\u{1b}' {
+            // CSI sequences are `ESC [ ... final-byte`, where the final
+            // byte is in `0x40..=0x7e`; consume through it and drop the
+            // whole sequence. For any other sequence only the ESC byte
+            // itself is dropped; the characters after it are kept.
+            if chars.as_str().starts_with('[') {
+                chars.next();
+                for c in chars.by_ref() {
+                    if ('\x40'..='\x7e').contains(&c) {
+                        break;
+                    }
+                }
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
 pub fn plural(n: usize, noun: &str) -> String {
     if n == 1 {
         format!("{n} {noun}")
@@ -613,4 +867,13 @@ mod test {
             "100:03"
         );
     }
+
+    #[test]
+    fn test_strip_ansi() {
+        assert_eq!(strip_ansi("plain text"), "plain text");
+        assert_eq!(
+            strip_ansi("\x1b[1;35mreplace\x1b[0m \x1b[33mfoo\x1b[0m"),
+            "replace foo"
+        );
+    }
 }
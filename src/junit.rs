@@ -0,0 +1,152 @@
+// Copyright 2023 Martin Pool
+
+//! Write the lab outcome as a JUnit XML report, so that CI systems that
+//! already understand `<testsuite>`/`<testcase>` output (as produced by e.g.
+//! `cargo2junit`) can show surviving mutants as failing tests without
+//! needing to parse cargo-mutants' own text output.
+
+use camino::Utf8Path;
+
+use crate::outcome::{LabOutcome, SummaryOutcome};
+use crate::*;
+
+/// Write `results.junit.xml` into `output_dir`, alongside the other
+/// `mutants.out` files.
+pub fn write_junit_report(lab_outcome: &LabOutcome, output_dir: &Utf8Path) -> Result<()> {
+    let xml = junit_xml(lab_outcome);
+    let path = output_dir.join("results.junit.xml");
+    fs::write(&path, xml).with_context(|| format!("failed to write {path:?}"))?;
+    Ok(())
+}
+
+/// Render the lab outcome as a `<testsuite>` XML document.
+fn junit_xml(lab_outcome: &LabOutcome) -> String {
+    let outcomes = lab_outcome.outcomes();
+    // Keep these counts in lockstep with the `<failure>`/`<error>`/`<skipped>`
+    // elements that `testcase_xml` actually emits for each summary, so the
+    // `<testsuite>` attributes never disagree with its own children.
+    let n_failures = outcomes
+        .iter()
+        .filter(|o| matches!(o.summary(), SummaryOutcome::MissedMutant | SummaryOutcome::Timeout))
+        .count();
+    let n_errors = outcomes
+        .iter()
+        .filter(|o| o.summary() == SummaryOutcome::Failure)
+        .count();
+    let n_skipped = outcomes
+        .iter()
+        .filter(|o| o.summary() == SummaryOutcome::Unviable)
+        .count();
+    let total_time: f64 = outcomes
+        .iter()
+        .flat_map(|o| o.phase_results())
+        .map(|pr| pr.duration.as_secs_f64())
+        .sum();
+
+    let mut xml = String::with_capacity(200 + outcomes.len() * 200);
+    xml.push_str(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    xml.push('\n');
+    xml.push_str(&format!(
+        r#"<testsuite name="cargo-mutants" tests="{tests}" failures="{n_failures}" errors="{n_errors}" skipped="{n_skipped}" time="{total_time:.3}">"#,
+        tests = outcomes.len(),
+    ));
+    xml.push('\n');
+    for outcome in outcomes {
+        xml.push_str(&testcase_xml(outcome));
+    }
+    xml.push_str("</testsuite>\n");
+    xml
+}
+
+fn testcase_xml(outcome: &ScenarioOutcome) -> String {
+    let name = xml_escape(&plain_scenario_description(&outcome.scenario));
+    let classname = xml_escape(&scenario_classname(&outcome.scenario));
+    let time: f64 = outcome
+        .phase_results()
+        .iter()
+        .map(|pr| pr.duration.as_secs_f64())
+        .sum();
+    let mut s = format!(r#"  <testcase name="{name}" classname="{classname}" time="{time:.3}">"#);
+    s.push('\n');
+    match outcome.summary() {
+        SummaryOutcome::MissedMutant | SummaryOutcome::Timeout => {
+            let message = xml_escape(outcome.summary_text());
+            let body = xml_escape(&log_tail(outcome));
+            s.push_str(&format!(
+                "    <failure message=\"{message}\">{body}</failure>\n"
+            ));
+        }
+        SummaryOutcome::Unviable => {
+            s.push_str("    <skipped/>\n");
+        }
+        SummaryOutcome::Failure => {
+            // The scenario itself errored out (for example the baseline's
+            // own tests failed) rather than a mutant being caught or
+            // missed; that's still a broken run and must not look green.
+            let message = xml_escape(outcome.summary_text());
+            let body = xml_escape(&log_tail(outcome));
+            s.push_str(&format!(
+                "    <error message=\"{message}\">{body}</error>\n"
+            ));
+        }
+        SummaryOutcome::CaughtMutant | SummaryOutcome::Success => {}
+    }
+    s.push_str("  </testcase>\n");
+    s
+}
+
+/// The scenario description with ANSI styling stripped, since the XML
+/// report is consumed by machines rather than a terminal.
+fn plain_scenario_description(scenario: &Scenario) -> String {
+    console::plain_scenario(scenario)
+}
+
+fn scenario_classname(scenario: &Scenario) -> String {
+    match scenario {
+        Scenario::Baseline => "baseline".to_owned(),
+        Scenario::Mutant(mutant) => format!(
+            "{}::{}",
+            mutant.source_file_path(),
+            mutant.function_name()
+        ),
+    }
+}
+
+/// The last few lines of the scenario's log, used as the failure message
+/// body so a CI viewer shows useful context without opening the log file.
+///
+/// Cargo's own output is often colored, so the tail is run through
+/// [console::strip_ansi] before being embedded in the report.
+fn log_tail(outcome: &ScenarioOutcome) -> String {
+    outcome
+        .get_log_content()
+        .map(|content| {
+            let content = console::strip_ansi(&content);
+            content
+                .lines()
+                .rev()
+                .take(20)
+                .collect::<Vec<_>>()
+                .into_iter()
+                .rev()
+                .collect::<Vec<_>>()
+                .join("\n")
+        })
+        .unwrap_or_default()
+}
+
+/// Escape text for use in XML attribute values and element content.
+///
+/// As well as entity-escaping, this drops control characters other than
+/// tab/LF/CR, which [XML 1.0](https://www.w3.org/TR/xml/#charsets) simply
+/// does not allow anywhere in a document; left in, they'd produce a file
+/// that strict parsers reject outright.
+fn xml_escape(s: &str) -> String {
+    s.chars()
+        .filter(|&c| c == '\t' || c == '\n' || c == '\r' || !c.is_control())
+        .collect::<String>()
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,284 @@
+// Copyright 2021, 2022, 2023 Martin Pool
+
+//! Structured reporting of lab events.
+//!
+//! [Console] drives the terminal progress bar directly, but it also fans
+//! every event out to a list of [Reporter] implementations so that other
+//! consumers -- such as a CI system reading a NDJSON stream -- can observe
+//! the same events without scraping the human-oriented text.
+
+use std::fs::File;
+use std::io::{self, Write};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use camino::Utf8Path;
+use serde::Serialize;
+
+use crate::outcome::{LabOutcome, SummaryOutcome};
+use crate::*;
+
+/// Receives structured events about the progress of a mutation testing run.
+///
+/// Methods mirror the notifications that [crate::console::Console] itself
+/// needs, so that the terminal view can be implemented as just another
+/// `Reporter` alongside e.g. [JsonlReporter].
+///
+/// All methods have a default no-op implementation so that a reporter only
+/// interested in a subset of events doesn't have to implement the rest.
+pub trait Reporter: Send + Sync {
+    /// A scenario (the baseline, or a mutant) started running.
+    fn scenario_started(&self, _scenario: &Scenario, _log_file: &Utf8Path) {}
+
+    /// A phase of a scenario (build, test, ...) started.
+    fn scenario_phase_started(&self, _scenario: &Scenario, _phase: Phase) {}
+
+    /// A phase of a scenario finished.
+    fn scenario_phase_finished(&self, _scenario: &Scenario, _phase: Phase) {}
+
+    /// A scenario finished, with the given outcome.
+    fn scenario_finished(
+        &self,
+        _scenario: &Scenario,
+        _outcome: &ScenarioOutcome,
+        _options: &Options,
+    ) {
+    }
+
+    /// Mutants were discovered and are about to be tested.
+    ///
+    /// `shuffle_seed` is set if `--shuffle` randomized their order, so that
+    /// the distribution of results can be reproduced exactly by passing the
+    /// seed back in on a later run.
+    fn discovered_mutants(&self, _mutants: &[Mutant], _shuffle_seed: Option<u64>) {}
+
+    /// The whole lab run finished.
+    fn lab_finished(&self, _lab_outcome: &LabOutcome, _start_time: Instant, _options: &Options) {}
+}
+
+/// A single line of the NDJSON event stream.
+///
+/// One of these is written per event, so that a reader doesn't need to
+/// buffer the whole run to start processing it, and corrupted or truncated
+/// output only loses the trailing event rather than the whole file.
+#[derive(Serialize)]
+struct JsonEvent<'a> {
+    event: &'a str,
+    elapsed_ms: u128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scenario: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mutant: Option<JsonMutant>,
+    /// The full set of planned mutants, carried only on `discovered_mutants`,
+    /// so a dashboard can enumerate the planned work up front rather than
+    /// having to wait for and accumulate every `scenario_started` event.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mutants: Option<Vec<JsonMutant>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    n_mutants: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    outcome: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    phase: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    phase_duration_ms: Option<u128>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    shuffle_seed: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct JsonMutant {
+    file: String,
+    line: usize,
+    function: String,
+    replacement: String,
+}
+
+impl JsonMutant {
+    fn from_mutant(mutant: &Mutant) -> JsonMutant {
+        JsonMutant {
+            file: mutant.source_file_path().to_string(),
+            line: mutant.span().start.line,
+            function: mutant.function_name().to_owned(),
+            replacement: mutant.replacement_text().to_owned(),
+        }
+    }
+}
+
+/// A [Reporter] that writes one JSON object per line to a file or other
+/// writer, independent of the nutmeg terminal view.
+///
+/// The writer is wrapped in a mutex because events can arrive from any
+/// thread running a scenario; each line is written and flushed under the
+/// lock so interleaved writes never corrupt a single JSON object.
+pub struct JsonlReporter {
+    sink: Mutex<Box<dyn Write + Send>>,
+    start_time: Instant,
+}
+
+impl JsonlReporter {
+    /// Create a reporter that appends NDJSON events to a newly-created file.
+    pub fn create_file(path: &Utf8Path) -> Result<JsonlReporter> {
+        let file = File::create(path)
+            .with_context(|| format!("failed to create JSONL report {path:?}"))?;
+        Ok(JsonlReporter::new(Box::new(file)))
+    }
+
+    /// Create a reporter that writes NDJSON events to an arbitrary writer,
+    /// for example `io::stderr()`.
+    pub fn new(sink: Box<dyn Write + Send>) -> JsonlReporter {
+        JsonlReporter {
+            sink: Mutex::new(sink),
+            start_time: Instant::now(),
+        }
+    }
+
+    fn write_event(&self, event: JsonEvent) {
+        // Best-effort: a reporting sink going away (e.g. a closed fd)
+        // shouldn't abort the mutation run.
+        let mut sink = self.sink.lock().unwrap();
+        if let Ok(line) = serde_json::to_string(&event) {
+            let _ = writeln!(sink, "{line}");
+            let _ = sink.flush();
+        }
+    }
+
+    fn elapsed_ms(&self) -> u128 {
+        self.start_time.elapsed().as_millis()
+    }
+}
+
+impl Reporter for JsonlReporter {
+    fn scenario_started(&self, scenario: &Scenario, _log_file: &Utf8Path) {
+        self.write_event(JsonEvent {
+            event: "scenario_started",
+            elapsed_ms: self.elapsed_ms(),
+            scenario: Some(console::plain_scenario(scenario)),
+            mutant: scenario.mutant().map(JsonMutant::from_mutant),
+            outcome: None,
+            phase: None,
+            phase_duration_ms: None,
+            shuffle_seed: None,
+            mutants: None,
+            n_mutants: None,
+        });
+    }
+
+    fn scenario_phase_started(&self, scenario: &Scenario, phase: Phase) {
+        self.write_event(JsonEvent {
+            event: "phase_started",
+            elapsed_ms: self.elapsed_ms(),
+            scenario: Some(console::plain_scenario(scenario)),
+            mutant: None,
+            outcome: None,
+            phase: Some(phase.name()),
+            phase_duration_ms: None,
+            shuffle_seed: None,
+            mutants: None,
+            n_mutants: None,
+        });
+    }
+
+    fn scenario_phase_finished(&self, scenario: &Scenario, phase: Phase) {
+        self.write_event(JsonEvent {
+            event: "phase_finished",
+            elapsed_ms: self.elapsed_ms(),
+            scenario: Some(console::plain_scenario(scenario)),
+            mutant: None,
+            outcome: None,
+            phase: Some(phase.name()),
+            phase_duration_ms: None,
+            shuffle_seed: None,
+            mutants: None,
+            n_mutants: None,
+        });
+    }
+
+    fn scenario_finished(&self, scenario: &Scenario, outcome: &ScenarioOutcome, _options: &Options) {
+        for pr in outcome.phase_results() {
+            self.write_event(JsonEvent {
+                event: "phase_duration",
+                elapsed_ms: self.elapsed_ms(),
+                scenario: Some(console::plain_scenario(scenario)),
+                mutant: None,
+                outcome: None,
+                phase: Some(pr.phase.name()),
+                phase_duration_ms: Some(pr.duration.as_millis()),
+                shuffle_seed: None,
+                mutants: None,
+                n_mutants: None,
+            });
+        }
+        self.write_event(JsonEvent {
+            event: "scenario_finished",
+            elapsed_ms: self.elapsed_ms(),
+            scenario: Some(console::plain_scenario(scenario)),
+            mutant: scenario.mutant().map(JsonMutant::from_mutant),
+            outcome: Some(outcome_name(outcome.summary())),
+            phase: None,
+            phase_duration_ms: None,
+            shuffle_seed: None,
+            mutants: None,
+            n_mutants: None,
+        });
+    }
+
+    fn discovered_mutants(&self, mutants: &[Mutant], shuffle_seed: Option<u64>) {
+        self.write_event(JsonEvent {
+            event: "discovered_mutants",
+            elapsed_ms: self.elapsed_ms(),
+            scenario: None,
+            mutant: None,
+            mutants: Some(mutants.iter().map(JsonMutant::from_mutant).collect()),
+            n_mutants: Some(mutants.len()),
+            outcome: None,
+            phase: None,
+            phase_duration_ms: None,
+            shuffle_seed,
+        });
+    }
+
+    fn lab_finished(&self, _lab_outcome: &LabOutcome, _start_time: Instant, _options: &Options) {
+        self.write_event(JsonEvent {
+            event: "lab_finished",
+            elapsed_ms: self.elapsed_ms(),
+            scenario: None,
+            mutant: None,
+            outcome: None,
+            phase: None,
+            phase_duration_ms: None,
+            shuffle_seed: None,
+            mutants: None,
+            n_mutants: None,
+        });
+    }
+}
+
+fn outcome_name(summary: SummaryOutcome) -> &'static str {
+    match summary {
+        SummaryOutcome::CaughtMutant => "caught",
+        SummaryOutcome::MissedMutant => "missed",
+        SummaryOutcome::Timeout => "timeout",
+        SummaryOutcome::Unviable => "unviable",
+        SummaryOutcome::Success => "success",
+        SummaryOutcome::Failure => "failure",
+    }
+}
+
+/// A [Reporter] that writes nothing; used as a placeholder when no extra
+/// reporting sink was requested.
+pub struct NullReporter;
+
+impl Reporter for NullReporter {}
+
+/// Open the NDJSON sink named by `--emit-json`: either a regular file path,
+/// or `-` for stderr.
+pub fn open_jsonl_sink(dest: &str) -> Result<Box<dyn Write + Send>> {
+    if dest == "-" {
+        Ok(Box::new(io::stderr()))
+    } else {
+        Ok(Box::new(
+            File::create(dest).with_context(|| format!("failed to create {dest:?}"))?,
+        ))
+    }
+}
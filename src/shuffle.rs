@@ -0,0 +1,35 @@
+// Copyright 2023 Martin Pool
+
+//! `--shuffle` / `--shuffle-seed`: randomize mutant testing order, the same
+//! way Deno's test runner shuffles specifiers so that order-dependence bugs
+//! show up sooner, while still letting a user reproduce one run's ordering
+//! exactly by passing back the seed it printed.
+
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::*;
+
+/// Shuffle `mutants` in place if `options.shuffle` is set, and return the
+/// seed that was used, if any.
+///
+/// This is applied once to the whole discovered list, before any scenarios
+/// are started, so that the baseline (always run first, separately) isn't
+/// affected by the shuffle.
+///
+/// The shuffle is driven by `ChaCha8Rng` rather than `rand`'s default or
+/// `SmallRng`: both of those are explicitly documented as unstable across
+/// platforms, pointer widths, and `rand` versions, which would silently
+/// break the promise that passing the same seed back reproduces the same
+/// order. `rand_chacha`'s generators are specified algorithms with a fixed
+/// output stream, so a seed reproduces identically on any host, forever.
+pub fn maybe_shuffle(mutants: &mut [Mutant], options: &Options) -> Option<u64> {
+    if !options.shuffle {
+        return None;
+    }
+    let seed = options.shuffle_seed.unwrap_or_else(|| rand::thread_rng().gen());
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    mutants.shuffle(&mut rng);
+    Some(seed)
+}
@@ -0,0 +1,213 @@
+// Copyright 2023 Martin Pool
+
+//! `--watch` mode: after the initial run, keep the process alive and
+//! re-test only the mutants affected by files that change, similar to how
+//! Deno's `file_watcher` drives a rerun loop from filesystem events.
+
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use camino::{Utf8Path, Utf8PathBuf};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::*;
+
+/// How long to wait after the first change event for more to arrive, so a
+/// save-all or a branch switch is seen as a single batch rather than one
+/// restart per file.
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// How often the watcher checks whether a new change arrived while a batch
+/// is running, so it can cancel promptly rather than waiting for the batch
+/// to finish on its own.
+const CANCEL_POLL: Duration = Duration::from_millis(50);
+
+/// Shared between the watch loop and an in-flight `run_batch` call, so that
+/// a change arriving mid-run can ask the scenarios in progress to stop
+/// early instead of being queued up behind them.
+#[derive(Clone, Default)]
+pub struct CancelFlag(Arc<AtomicBool>);
+
+impl CancelFlag {
+    fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    /// True once a later change has made this batch stale; scenario
+    /// execution should check this between (or during) mutants and bail
+    /// out as soon as it's set.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// Watch `source_tree` for changes and re-test the mutants affected by each
+/// batch of changes, until the process is interrupted.
+pub fn watch_and_retest(
+    source_tree: &Utf8Path,
+    mutants: &[Mutant],
+    console: &Console,
+    run_batch: impl Fn(&[&Mutant], &CancelFlag) -> Result<()> + Send + Sync,
+) -> Result<()> {
+    let source_tree = source_tree
+        .canonicalize_utf8()
+        .with_context(|| format!("failed to canonicalize {source_tree:?}"))?;
+    let (tx, rx) = channel();
+    let mut watcher: RecommendedWatcher = notify::recommended_watcher(move |res| {
+        if let Ok(event) = res {
+            let _ = tx.send(event);
+        }
+    })
+    .context("failed to start filesystem watcher")?;
+    watcher
+        .watch(source_tree.as_std_path(), RecursiveMode::Recursive)
+        .context("failed to watch source tree")?;
+
+    console.watching(count_watched_files(&source_tree), mutants.len());
+    // Changes that arrived while the previous batch was running, and so
+    // weren't yet reflected in a test run.
+    let mut pending: HashSet<Utf8PathBuf> = HashSet::new();
+    loop {
+        if pending.is_empty() {
+            // Block for the first event, then debounce by draining anything
+            // else that arrives within the debounce window.
+            let first = match rx.recv() {
+                Ok(event) => event,
+                Err(_) => return Ok(()), // watcher was dropped, e.g. in tests
+            };
+            add_changed_paths(&first, &source_tree, &mut pending);
+            loop {
+                match rx.recv_timeout(DEBOUNCE) {
+                    Ok(event) => add_changed_paths(&event, &source_tree, &mut pending),
+                    Err(RecvTimeoutError::Timeout | RecvTimeoutError::Disconnected) => break,
+                }
+            }
+        }
+        let changed = std::mem::take(&mut pending);
+        let affected = affected_mutants(mutants, &changed);
+        if affected.is_empty() {
+            continue;
+        }
+        console.resume_watching_batch(affected.len());
+
+        // Run the batch on another thread so this loop can keep watching
+        // for filesystem events; if one arrives before the batch finishes,
+        // set the cancel flag (observed by `run_batch`) and remember the
+        // change for the next iteration instead of waiting for a full
+        // debounce window.
+        let cancel = CancelFlag::default();
+        thread::scope(|scope| -> Result<()> {
+            let handle = scope.spawn(|| run_batch(&affected, &cancel));
+            loop {
+                if handle.is_finished() {
+                    break;
+                }
+                match rx.recv_timeout(CANCEL_POLL) {
+                    Ok(event) => {
+                        cancel.cancel();
+                        add_changed_paths(&event, &source_tree, &mut pending);
+                    }
+                    Err(RecvTimeoutError::Timeout) => continue,
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+            }
+            handle.join().expect("run_batch thread panicked")
+        })?;
+
+        console.watching(count_watched_files(&source_tree), mutants.len());
+    }
+}
+
+/// Record the paths touched by a filesystem event, normalized to be
+/// relative to `source_tree` so they can be compared against
+/// `Mutant::source_file_path`, which is itself tree-relative.
+///
+/// `notify` reports paths rooted at the watched directory (effectively
+/// absolute, and canonicalized on most platforms); comparing those
+/// directly against a mutant's tree-relative path would never match.
+fn add_changed_paths(
+    event: &notify::Event,
+    source_tree: &Utf8Path,
+    changed: &mut HashSet<Utf8PathBuf>,
+) {
+    for path in &event.paths {
+        let Ok(path) = Utf8PathBuf::try_from(path.clone()) else {
+            continue;
+        };
+        let path = path.canonicalize_utf8().unwrap_or(path);
+        if let Ok(relative) = path.strip_prefix(source_tree) {
+            changed.insert(relative.to_owned());
+        }
+    }
+}
+
+/// The mutants, among `mutants`, whose source file is in `changed`.
+fn affected_mutants<'m>(mutants: &'m [Mutant], changed: &HashSet<Utf8PathBuf>) -> Vec<&'m Mutant> {
+    mutants
+        .iter()
+        .filter(|mutant| changed.contains(mutant.source_file_path()))
+        .collect()
+}
+
+/// Directory names that hold generated or VCS metadata rather than source,
+/// and so shouldn't be descended into when counting watched files: walking
+/// into `target/` or `mutants.out/` in particular can dwarf the real source
+/// count by orders of magnitude.
+const EXCLUDED_DIRS: &[&str] = &[".git", "target", "mutants.out"];
+
+/// Count the number of source files under the tree, for the "watching for
+/// changes" status line.
+fn count_watched_files(source_tree: &Utf8Path) -> usize {
+    walkdir::WalkDir::new(source_tree)
+        .into_iter()
+        .filter_entry(|entry| {
+            !entry.file_type().is_dir()
+                || !matches!(entry.file_name().to_str(), Some(name) if EXCLUDED_DIRS.contains(&name))
+        })
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .count()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn changed_paths_are_normalized_relative_to_the_source_tree() {
+        let source_tree = Utf8Path::new(env!("CARGO_MANIFEST_DIR"));
+        let changed_file = source_tree.join("src").join("lib.rs");
+        let event = notify::Event {
+            paths: vec![changed_file.into_std_path_buf()],
+            ..Default::default()
+        };
+
+        let mut changed = HashSet::new();
+        add_changed_paths(&event, source_tree, &mut changed);
+
+        assert!(
+            changed.contains(Utf8Path::new("src/lib.rs")),
+            "expected {changed:?} to contain the tree-relative changed path"
+        );
+    }
+
+    #[test]
+    fn count_watched_files_excludes_target_and_git_and_mutants_out() {
+        let dir = Utf8PathBuf::from_path_buf(std::env::temp_dir())
+            .expect("temp dir is not UTF-8")
+            .join(format!("cargo-mutants-watch-test-{}", std::process::id()));
+        for sub in ["src", "target", ".git", "mutants.out"] {
+            std::fs::create_dir_all(dir.join(sub)).unwrap();
+            std::fs::write(dir.join(sub).join("file.txt"), b"content").unwrap();
+        }
+
+        let n = count_watched_files(&dir);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(n, 1, "only src/file.txt should be counted");
+    }
+}